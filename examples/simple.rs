@@ -44,7 +44,7 @@ fn main() -> Result<()> {
     data.nested.number = 42;
 
     // encode the block and do some random access
-    let block = Block::encode(&data, 80)?;
+    let block = Block::encode(&data)?;
     assert_eq!(block.nested.number, 42);
 
     // construct a query
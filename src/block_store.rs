@@ -0,0 +1,140 @@
+use crate::{extract_bytes, Block, Cid, Slice};
+use anyhow::Result;
+use async_trait::async_trait;
+use rkyv::Archive;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+pub trait SyncBlockStore {
+    fn get_slice(&self, cid: &Cid) -> Result<Box<[u8]>>;
+    fn put<T: Archive>(&self, block: &Block<T>) -> Result<()>;
+}
+
+#[async_trait]
+pub trait AsyncBlockStore: Send + Sync {
+    async fn get_slice(&self, cid: &Cid) -> Result<Box<[u8]>>;
+    async fn put<T: Archive + Send + Sync>(&self, block: &Block<T>) -> Result<()>;
+}
+
+// blanket-implemented over every SyncBlockStore so fetch's generic param
+// doesn't need to be dyn-safe itself
+pub trait BlockStore: SyncBlockStore {
+    fn fetch<T: Archive>(&self, cid: &Cid) -> Result<Slice<T>> {
+        let data = self.get_slice(cid)?;
+        Slice::decode(cid, &data)
+    }
+}
+
+impl<S: SyncBlockStore + ?Sized> BlockStore for S {}
+
+// SyncBlockStore::put is generic, so it isn't dyn-safe; this replaces it
+// with pre-encoded bytes and gets SyncBlockStore back via the blanket impl
+// below
+pub trait ErasedBlockStore: Send + Sync {
+    fn get_slice(&self, cid: &Cid) -> Result<Box<[u8]>>;
+    fn put_raw(&self, hash: [u8; 32], data: Box<[u8]>, outboard: Box<[u8]>);
+}
+
+impl<S: ErasedBlockStore + ?Sized> SyncBlockStore for S {
+    fn get_slice(&self, cid: &Cid) -> Result<Box<[u8]>> {
+        ErasedBlockStore::get_slice(self, cid)
+    }
+
+    fn put<T: Archive>(&self, block: &Block<T>) -> Result<()> {
+        let (data, outboard) = block.raw_parts();
+        self.put_raw(
+            *block.cid().hash().as_bytes(),
+            data.to_vec().into_boxed_slice(),
+            outboard.to_vec().into_boxed_slice(),
+        );
+        Ok(())
+    }
+}
+
+struct StoredBlock {
+    data: Box<[u8]>,
+    outboard: Box<[u8]>,
+}
+
+#[derive(Default)]
+pub struct MemoryBlockStore {
+    blocks: Mutex<HashMap<[u8; 32], StoredBlock>>,
+}
+
+impl MemoryBlockStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ErasedBlockStore for MemoryBlockStore {
+    fn get_slice(&self, cid: &Cid) -> Result<Box<[u8]>> {
+        let blocks = self.blocks.lock().unwrap();
+        let hash = *cid.hash().as_bytes();
+        let stored = blocks
+            .get(&hash)
+            .ok_or_else(|| anyhow::anyhow!("block not found: {}", cid))?;
+        extract_bytes(&stored.data, &stored.outboard, cid.start(), cid.len())
+    }
+
+    fn put_raw(&self, hash: [u8; 32], data: Box<[u8]>, outboard: Box<[u8]>) {
+        self.blocks
+            .lock()
+            .unwrap()
+            .insert(hash, StoredBlock { data, outboard });
+    }
+}
+
+#[async_trait]
+impl AsyncBlockStore for MemoryBlockStore {
+    async fn get_slice(&self, cid: &Cid) -> Result<Box<[u8]>> {
+        ErasedBlockStore::get_slice(self, cid)
+    }
+
+    async fn put<T: Archive + Send + Sync>(&self, block: &Block<T>) -> Result<()> {
+        SyncBlockStore::put(self, block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use memoffset::span_of;
+    use rkyv::Archived;
+
+    #[derive(Archive, Default, PartialEq)]
+    struct AStruct {
+        number: u32,
+    }
+
+    impl crate::Selectable for AStruct {
+        fn select(cid: &Cid, field: &str) -> Result<Cid> {
+            Ok(match field {
+                "number" => cid.slice(span_of!(Archived<Self>, number)),
+                _ => anyhow::bail!("invalid key"),
+            })
+        }
+    }
+
+    #[test]
+    fn fetch_roundtrips_through_memory_store() -> Result<()> {
+        let mut data = AStruct::default();
+        data.number = 7;
+        let block = Block::encode(&data)?;
+        let cid = *block.cid();
+
+        let store = MemoryBlockStore::new();
+        SyncBlockStore::put(&store, &block)?;
+
+        let query = cid.select::<AStruct>("number")?;
+        let number = store.fetch::<u32>(&query)?;
+        assert_eq!(*number, 7);
+        Ok(())
+    }
+
+    #[test]
+    fn get_slice_on_unknown_cid_fails() {
+        let store = MemoryBlockStore::new();
+        assert!(SyncBlockStore::get_slice(&store, &Cid::default()).is_err());
+    }
+}
@@ -0,0 +1,21 @@
+// ChaCha20, not a block AEAD: ciphertext must stay the same length as
+// plaintext so Cid ranges still line up.
+
+use chacha20::cipher::{NewCipher, StreamCipher, StreamCipherSeek};
+use chacha20::ChaCha20;
+
+// nonce is always zero, so each key must only ever encrypt one plaintext
+pub type Key = [u8; 32];
+
+// convergent: BLAKE3(plaintext), so identical content always yields the
+// same ciphertext and Cid -- leaks equality of identical plaintexts to
+// anyone who can compare them
+pub fn convergent_key(plaintext: &[u8]) -> Key {
+    *blake3::hash(plaintext).as_bytes()
+}
+
+pub(crate) fn apply_keystream(key: &Key, offset: u64, buf: &mut [u8]) {
+    let mut cipher = ChaCha20::new(key.into(), &[0u8; 12].into());
+    cipher.seek(offset);
+    cipher.apply_keystream(buf);
+}
@@ -0,0 +1,199 @@
+use crate::{extract_bytes, Block, Cid};
+use anyhow::{anyhow, Result};
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use rkyv::Archive;
+use std::convert::TryInto;
+use std::marker::PhantomData;
+
+#[derive(Clone, Debug)]
+pub struct Shard {
+    pub index: usize,
+    pub data: Box<[u8]>,
+    pub cid: Cid,
+}
+
+pub struct ErasureCoding<T: Archive> {
+    concatenated: Box<[u8]>,
+    outboard: Box<[u8]>,
+    commitment: Cid,
+    k: usize,
+    m: usize,
+    shard_len: usize,
+    marker: PhantomData<T>,
+}
+
+impl<T: Archive> ErasureCoding<T> {
+    // every Shard::cid is a sub-range slice of this commitment
+    pub fn commitment(&self) -> Cid {
+        self.commitment
+    }
+
+    pub fn shards(&self) -> Vec<Shard> {
+        (0..self.k + self.m).map(|index| self.shard(index)).collect()
+    }
+
+    fn shard(&self, index: usize) -> Shard {
+        let start = index * self.shard_len;
+        let end = start + self.shard_len;
+        Shard {
+            index,
+            data: self.concatenated[start..end].to_vec().into_boxed_slice(),
+            cid: self.commitment.slice(start..end),
+        }
+    }
+
+    pub fn extract_shard(&self, shard: &Shard) -> Result<Box<[u8]>> {
+        extract_bytes(
+            &self.concatenated,
+            &self.outboard,
+            shard.cid.start(),
+            shard.cid.len(),
+        )
+    }
+}
+
+impl<T: Archive> Block<T> {
+    pub fn erasure_encode(&self, k: usize, m: usize) -> Result<ErasureCoding<T>> {
+        let (data, _) = self.raw_parts();
+
+        // original length as an 8-byte little-endian prefix, so
+        // erasure_recover can strip the shard padding back off
+        let mut framed = Vec::with_capacity(8 + data.len());
+        framed.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        framed.extend_from_slice(data);
+
+        let shard_len = (framed.len() + k - 1) / k;
+        framed.resize(shard_len * k, 0);
+
+        let mut shards: Vec<Vec<u8>> = framed
+            .chunks(shard_len)
+            .map(|chunk| chunk.to_vec())
+            .collect();
+        shards.extend((0..m).map(|_| vec![0u8; shard_len]));
+
+        let rs = ReedSolomon::new(k, m)?;
+        rs.encode(&mut shards)?;
+
+        let concatenated: Vec<u8> = shards.into_iter().flatten().collect();
+        let (outboard, hash) = bao::encode::outboard(&concatenated);
+        let commitment = Cid::new(*hash.as_bytes(), concatenated.len());
+
+        Ok(ErasureCoding {
+            concatenated: concatenated.into_boxed_slice(),
+            outboard: outboard.into_boxed_slice(),
+            commitment,
+            k,
+            m,
+            shard_len,
+            marker: PhantomData,
+        })
+    }
+
+    pub fn erasure_recover(shards: &[(usize, Box<[u8]>)], k: usize, m: usize) -> Result<Block<T>> {
+        if shards.len() < k {
+            anyhow::bail!("need at least {} shards, got {}", k, shards.len());
+        }
+        let shard_len = shards[0].1.len();
+        if shards.iter().any(|(_, data)| data.len() != shard_len) {
+            anyhow::bail!("all shards must be the same length");
+        }
+
+        let mut option_shards: Vec<Option<Vec<u8>>> = vec![None; k + m];
+        for (index, data) in shards {
+            if *index >= k + m {
+                anyhow::bail!("shard index {} out of range for k + m = {}", index, k + m);
+            }
+            option_shards[*index] = Some(data.to_vec());
+        }
+
+        let rs = ReedSolomon::new(k, m)?;
+        rs.reconstruct(&mut option_shards)?;
+
+        let mut framed = Vec::with_capacity(shard_len * k);
+        for shard in option_shards.into_iter().take(k) {
+            framed.extend(shard.ok_or_else(|| anyhow!("reconstruction left a data shard empty"))?);
+        }
+
+        let original_len = u64::from_le_bytes(framed[0..8].try_into()?) as usize;
+        let data = framed
+            .get(8..8 + original_len)
+            .ok_or_else(|| anyhow!("corrupt length prefix: {} bytes, only {} available", original_len, framed.len().saturating_sub(8)))?
+            .to_vec();
+        Ok(Block::new(data.into_boxed_slice()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use memoffset::span_of;
+    use rkyv::Archived;
+
+    #[derive(rkyv::Archive, Default, PartialEq)]
+    struct AStruct {
+        number: u32,
+        text: String,
+    }
+
+    impl crate::Selectable for AStruct {
+        fn select(cid: &Cid, field: &str) -> Result<Cid> {
+            Ok(match field {
+                "number" => cid.slice(span_of!(Archived<Self>, number)),
+                "text" => cid.slice(span_of!(Archived<Self>, text)),
+                _ => anyhow::bail!("invalid key"),
+            })
+        }
+    }
+
+    #[test]
+    fn recovers_from_any_k_shards() -> Result<()> {
+        let mut data = AStruct::default();
+        data.number = 42;
+        data.text = "hello erasure coding".to_string();
+        let block = Block::encode(&data)?;
+
+        let coding = block.erasure_encode(4, 2)?;
+        let shards = coding.shards();
+
+        // drop two shards (as many as we can tolerate with m = 2)
+        let surviving: Vec<(usize, Box<[u8]>)> = shards
+            .iter()
+            .skip(2)
+            .map(|s| (s.index, s.data.clone()))
+            .collect();
+
+        let recovered = Block::<AStruct>::erasure_recover(&surviving, 4, 2)?;
+        assert_eq!(recovered.cid().hash(), block.cid().hash());
+        assert_eq!(recovered.number, 42);
+        Ok(())
+    }
+
+    #[test]
+    fn shards_are_disjoint_ranges_of_the_commitment() -> Result<()> {
+        let mut data = AStruct::default();
+        data.number = 7;
+        let block = Block::encode(&data)?;
+
+        let coding = block.erasure_encode(4, 2)?;
+        let shards = coding.shards();
+        for (i, shard) in shards.iter().enumerate() {
+            assert_eq!(shard.cid.hash(), coding.commitment().hash());
+            assert_eq!(shard.index, i);
+            assert_eq!(shard.cid.start(), i as u64 * shard.cid.len());
+
+            // verify the bao proof the same way `Slice::decode` would, and
+            // check it actually authenticates this shard's bytes.
+            let response = coding.extract_shard(shard)?;
+            let mut decoder = bao::decode::SliceDecoder::new(
+                &response[..],
+                &shard.cid.hash(),
+                shard.cid.start(),
+                shard.cid.len(),
+            );
+            let mut verified = Vec::new();
+            std::io::Read::read_to_end(&mut decoder, &mut verified)?;
+            assert_eq!(verified, &*shard.data);
+        }
+        Ok(())
+    }
+}
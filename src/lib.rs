@@ -1,10 +1,15 @@
+pub mod block_store;
+pub mod crypto;
+pub mod erasure;
+pub mod resolver;
+
 use anyhow::Result;
 use bao::decode::SliceDecoder;
 use bao::encode::SliceExtractor;
 use bao::Hash;
-use rkyv::{archived_value, Archive, ArchiveBuffer, Archived, Write, WriteExt};
+use rkyv::{archived_value, Archive, Archived, Write, WriteExt};
 use std::convert::TryInto;
-use std::io::{Cursor, Read};
+use std::io::{Cursor, Read, Write as IoWrite};
 use std::marker::PhantomData;
 use std::ops::{Deref, Range};
 
@@ -13,7 +18,7 @@ pub const EMPTY_BLOCK_HASH: [u8; 32] = [
     173, 193, 18, 183, 204, 154, 147, 202, 228, 31, 50, 98,
 ];
 
-#[derive(Archive, Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Archive, Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
 pub struct Cid {
     version: u8,
     hash: [u8; 32],
@@ -80,6 +85,17 @@ impl Default for Cid {
     }
 }
 
+impl From<&Archived<Cid>> for Cid {
+    fn from(archived: &Archived<Cid>) -> Self {
+        Self {
+            version: archived.version,
+            hash: archived.hash,
+            start: archived.start,
+            len: archived.len,
+        }
+    }
+}
+
 pub struct Slice<T> {
     data: Box<[u8]>,
     marker: PhantomData<T>,
@@ -97,6 +113,20 @@ impl<T: Archive> Slice<T> {
             marker: PhantomData,
         })
     }
+
+    // for Block::encode_encrypted / Block::encode_convergent: verify the
+    // ciphertext range, then decrypt it in place
+    pub fn decode_encrypted(cid: &Cid, data: &[u8], key: &crypto::Key) -> Result<Self> {
+        let mut buf = Vec::with_capacity(cid.len().try_into()?);
+        let mut decoder = SliceDecoder::new(data, &cid.hash(), cid.start(), cid.len());
+        decoder.read_to_end(&mut buf)?;
+        assert_eq!(buf.len(), cid.len() as usize);
+        crypto::apply_keystream(key, cid.start(), &mut buf);
+        Ok(Self {
+            data: buf.into_boxed_slice(),
+            marker: PhantomData,
+        })
+    }
 }
 
 impl<T: Archive> Deref for Slice<T> {
@@ -133,29 +163,63 @@ impl<T: Archive> Block<T> {
         &self.cid
     }
 
-    pub fn encode(value: &T, max_buf_size: usize) -> Result<Self> {
-        //let size = bao::encode::encoded_size(value.max_encoded_size());
-        //let mut buf = Vec::with_capacity(size.try_into()?);
-        let mut buf = vec![0; max_buf_size];
-        let mut encoder = ArchiveBuffer::new(&mut buf);
-        encoder.archive(value).unwrap();
-        let len = encoder.pos();
-        buf.resize(len, 0);
+    // grows the buffer as needed instead of guessing a max size up front
+    pub fn encode(value: &T) -> Result<Self> {
+        let mut buf = Vec::new();
+        buf.archive(value).unwrap();
+        Ok(Self::new(buf.into_boxed_slice()))
+    }
+
+    // like encode, but writes the combined bao encoding to writer and returns
+    // its Cid instead of building a Block; the encoding is built in memory
+    // before it is written
+    pub fn encode_to<W: std::io::Write>(value: &T, mut writer: W) -> Result<Cid> {
+        let mut buf = Vec::new();
+        buf.archive(value).unwrap();
 
+        let (encoded, hash) = bao::encode::encode(&buf);
+        writer.write_all(&encoded)?;
+        Ok(Cid::new(*hash.as_bytes(), buf.len()))
+    }
+
+    // cid commits to ciphertext, not plaintext; extract is unchanged
+    pub fn encode_encrypted(value: &T, key: crypto::Key) -> Result<Self> {
+        let mut buf = Vec::new();
+        buf.archive(value).unwrap();
+
+        crypto::apply_keystream(&key, 0, &mut buf);
         Ok(Self::new(buf.into_boxed_slice()))
     }
 
+    // see crypto::convergent_key for the dedup-vs-leak tradeoff this implies
+    pub fn encode_convergent(value: &T) -> Result<(Self, crypto::Key)> {
+        let mut buf = Vec::new();
+        buf.archive(value).unwrap();
+
+        let key = crypto::convergent_key(&buf);
+        crypto::apply_keystream(&key, 0, &mut buf);
+        Ok((Self::new(buf.into_boxed_slice()), key))
+    }
+
     pub fn extract(&self, start: u64, len: u64) -> Result<Box<[u8]>> {
-        let input = Cursor::new(&self.data);
-        let outboard = Cursor::new(&self.outboard);
-        let mut extractor = SliceExtractor::new_outboard(input, outboard, start, len);
-        let size = bao::encode::encoded_size(len).try_into()?;
-        let mut buf = Vec::with_capacity(size);
-        extractor.read_to_end(&mut buf)?;
-        Ok(buf.into_boxed_slice())
+        extract_bytes(&self.data, &self.outboard, start, len)
+    }
+
+    pub(crate) fn raw_parts(&self) -> (&[u8], &[u8]) {
+        (&self.data, &self.outboard)
     }
 }
 
+pub(crate) fn extract_bytes(data: &[u8], outboard: &[u8], start: u64, len: u64) -> Result<Box<[u8]>> {
+    let input = Cursor::new(data);
+    let outboard = Cursor::new(outboard);
+    let mut extractor = SliceExtractor::new_outboard(input, outboard, start, len);
+    let size = bao::encode::encoded_size(len).try_into()?;
+    let mut buf = Vec::with_capacity(size);
+    extractor.read_to_end(&mut buf)?;
+    Ok(buf.into_boxed_slice())
+}
+
 impl<T: Archive> Deref for Block<T> {
     type Target = Archived<T>;
 
@@ -220,7 +284,7 @@ mod tests {
         data.nested.number = 42;
 
         // encode the block and do some random access
-        let block = Block::encode(&data, 80)?;
+        let block = Block::encode(&data)?;
         assert_eq!(block.nested.number, 42);
 
         // construct a query
@@ -240,11 +304,11 @@ mod tests {
     fn test_authentication() -> Result<()> {
         let mut data = AStruct::default();
         data.nested.number = 42;
-        let block = Block::encode(&data, 80)?;
+        let block = Block::encode(&data)?;
         let cid = *block.cid();
 
         data.nested.number = 43;
-        let block = Block::encode(&data, 80)?;
+        let block = Block::encode(&data)?;
         let query = cid
             .select::<AStruct>("nested")?
             .select::<BStruct>("number")?;
@@ -252,4 +316,77 @@ mod tests {
         assert!(Slice::<u32>::decode(&query, &response).is_err());
         Ok(())
     }
+
+    #[test]
+    fn test_encrypted_random_access() -> Result<()> {
+        let mut data = AStruct::default();
+        data.nested.number = 42;
+
+        let (block, key) = Block::encode_convergent(&data)?;
+        let query = block
+            .cid()
+            .select::<AStruct>("nested")?
+            .select::<BStruct>("number")?;
+        let response = block.extract(query.start(), query.len())?;
+        let number = Slice::<u32>::decode_encrypted(&query, &response, &key)?;
+        assert_eq!(*number, 42);
+
+        // decoding with the wrong key produces the wrong plaintext
+        let other_key = crypto::convergent_key(b"not the same content");
+        let number = Slice::<u32>::decode_encrypted(&query, &response, &other_key)?;
+        assert_ne!(*number, 42);
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_encrypted_with_caller_supplied_key() -> Result<()> {
+        let mut data = AStruct::default();
+        data.nested.number = 42;
+
+        let key = crypto::convergent_key(b"caller-supplied key material");
+        let block = Block::encode_encrypted(&data, key)?;
+        let query = block
+            .cid()
+            .select::<AStruct>("nested")?
+            .select::<BStruct>("number")?;
+        let response = block.extract(query.start(), query.len())?;
+        let number = Slice::<u32>::decode_encrypted(&query, &response, &key)?;
+        assert_eq!(*number, 42);
+
+        // same plaintext, different caller-supplied keys, different ciphertext
+        let other_key = crypto::convergent_key(b"a different key");
+        let other_block = Block::encode_encrypted(&data, other_key)?;
+        assert_ne!(block.cid().hash(), other_block.cid().hash());
+        Ok(())
+    }
+
+    #[test]
+    fn test_convergent_encryption_is_deterministic() -> Result<()> {
+        let mut data = AStruct::default();
+        data.nested.number = 42;
+
+        let (first, first_key) = Block::encode_convergent(&data)?;
+        let (second, second_key) = Block::encode_convergent(&data)?;
+        assert_eq!(first.cid().hash(), second.cid().hash());
+        assert_eq!(first_key, second_key);
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_to_matches_encode() -> Result<()> {
+        let mut data = AStruct::default();
+        data.nested.number = 42;
+
+        let mut stream = Vec::new();
+        let cid = Block::encode_to(&data, &mut stream)?;
+
+        let block = Block::encode(&data)?;
+        assert_eq!(cid.hash(), block.cid().hash());
+        assert_eq!(cid.len(), block.cid().len());
+
+        let mut decoded = Vec::new();
+        SliceDecoder::new(&stream[..], &cid.hash(), 0, cid.len()).read_to_end(&mut decoded)?;
+        assert_eq!(decoded, block.raw_parts().0);
+        Ok(())
+    }
 }
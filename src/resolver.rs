@@ -0,0 +1,149 @@
+// walks a `/`-separated field path across block boundaries, following
+// Cid-typed link fields via an ErasedBlockStore
+use crate::block_store::ErasedBlockStore;
+use crate::{Cid, Selectable, Slice};
+use anyhow::{anyhow, Result};
+use rkyv::Archive;
+use std::collections::HashMap;
+
+type SelectFn = fn(&Cid, &str) -> Result<Cid>;
+
+// maps a struct tag to its Selectable::select function, so Resolver can
+// dispatch on a linked block's type without compile-time knowledge of the
+// whole schema
+#[derive(Default)]
+pub struct Registry {
+    selectors: HashMap<String, SelectFn>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<T: Selectable>(&mut self, tag: &str) {
+        self.selectors.insert(tag.to_string(), T::select);
+    }
+
+    fn lookup(&self, tag: &str) -> Result<SelectFn> {
+        self.selectors
+            .get(tag)
+            .copied()
+            .ok_or_else(|| anyhow!("no type registered for tag {:?}", tag))
+    }
+}
+
+pub struct Resolver<'a> {
+    store: &'a dyn ErasedBlockStore,
+    registry: &'a Registry,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(store: &'a dyn ErasedBlockStore, registry: &'a Registry) -> Self {
+        Self { store, registry }
+    }
+
+    pub fn resolve<T: Selectable>(&self, root: &Cid, path: &str) -> Result<Box<[u8]>> {
+        let cid = self.resolve_cid::<T>(root, path)?;
+        self.store.get_slice(&cid)
+    }
+
+    // like resolve, but decodes the terminal field as U
+    pub fn resolve_as<T: Selectable, U: Archive>(
+        &self,
+        root: &Cid,
+        path: &str,
+    ) -> Result<Slice<U>> {
+        let cid = self.resolve_cid::<T>(root, path)?;
+        let data = self.store.get_slice(&cid)?;
+        Slice::decode(&cid, &data)
+    }
+
+    fn resolve_cid<T: Selectable>(&self, root: &Cid, path: &str) -> Result<Cid> {
+        let mut cid = *root;
+        let mut select: SelectFn = T::select;
+
+        for segment in path.split('/') {
+            // a bare field name (e.g. "number") never crosses a link, even
+            // if the field happens to hold a Cid -- Selectable::select
+            // erases that, so there's no type information at this point to
+            // detect it automatically. Crossing a link needs an explicit
+            // "field:Tag" segment naming the registered type on the other
+            // side.
+            let (field, link_tag) = match segment.split_once(':') {
+                Some((field, tag)) => (field, Some(tag)),
+                None => (segment, None),
+            };
+            cid = select(&cid, field)?;
+
+            if let Some(tag) = link_tag {
+                let data = self.store.get_slice(&cid)?;
+                let link = Slice::<Cid>::decode(&cid, &data)?;
+                cid = Cid::from(&*link);
+                select = self.registry.lookup(tag)?;
+            }
+        }
+
+        Ok(cid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_store::{MemoryBlockStore, SyncBlockStore};
+    use crate::Block;
+    use memoffset::span_of;
+    use rkyv::Archived;
+
+    #[derive(rkyv::Archive, Default, PartialEq)]
+    struct AStruct {
+        link: Cid,
+    }
+
+    impl Selectable for AStruct {
+        fn select(cid: &Cid, field: &str) -> Result<Cid> {
+            Ok(match field {
+                "link" => cid.slice(span_of!(Archived<Self>, link)),
+                _ => anyhow::bail!("invalid key"),
+            })
+        }
+    }
+
+    #[derive(rkyv::Archive, Default, PartialEq)]
+    struct BStruct {
+        number: u32,
+    }
+
+    impl Selectable for BStruct {
+        fn select(cid: &Cid, field: &str) -> Result<Cid> {
+            Ok(match field {
+                "number" => cid.slice(span_of!(Archived<Self>, number)),
+                _ => anyhow::bail!("invalid key"),
+            })
+        }
+    }
+
+    #[test]
+    fn resolves_a_path_across_a_link() -> Result<()> {
+        let mut b = BStruct::default();
+        b.number = 42;
+        let b_block = Block::encode(&b)?;
+
+        let mut a = AStruct::default();
+        a.link = *b_block.cid();
+        let a_block = Block::encode(&a)?;
+
+        let store = MemoryBlockStore::new();
+        store.put(&a_block)?;
+        store.put(&b_block)?;
+
+        let mut registry = Registry::new();
+        registry.register::<BStruct>("BStruct");
+
+        let resolver = Resolver::new(&store, &registry);
+        let number = resolver.resolve_as::<AStruct, u32>(a_block.cid(), "link:BStruct/number")?;
+        assert_eq!(*number, 42);
+        Ok(())
+    }
+}